@@ -0,0 +1,152 @@
+use core::str::from_utf8;
+
+use defmt::{error, info};
+use embassy_net::dns::{DnsQueryType, DnsSocket};
+use embassy_net::driver::Driver;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::Stack;
+use embassy_time::{Duration, Instant, Timer};
+use rust_mqtt::client::client::MqttClient;
+use rust_mqtt::client::client_config::{ClientConfig, MqttVersion};
+use rust_mqtt::utils::rng_generator::CountingRng;
+
+use crate::{
+    broadcast_net_status, GameResult, GameTime, NetStatus, TimeUpdate, SCORE_SIGNAL, TIME_SIGNAL,
+};
+
+const BROKER_HOST: &str = "marxin.eu";
+const BROKER_PORT: u16 = 1883;
+const SCORE_TOPIC: &str = "sparta/score";
+const CLIENT_ID: &str = "livesport-display";
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+// Caps how long a single `receive_message` call may block, so the PINGREQ
+// deadline below gets checked regularly even when the broker stays quiet.
+// Much smaller than KEEP_ALIVE so it never delays a ping, and small enough
+// that a live match's steady stream of score updates doesn't starve it.
+const SOCKET_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+enum SleepState {
+    FirstIteration,
+    AfterFailure,
+}
+
+/// Keeps a single MQTT session alive for as long as possible, reconnecting
+/// with backoff on any failure. Runs forever, mirroring the old HTTP polling
+/// loop's `SleepState` pattern but without the per-poll TLS cost.
+pub async fn run<D: Driver + 'static>(stack: &'static Stack<D>) -> ! {
+    let mut sleep_state = SleepState::FirstIteration;
+
+    loop {
+        let sleep_in_secs = match sleep_state {
+            SleepState::FirstIteration => 0,
+            SleepState::AfterFailure => 30,
+        };
+        Timer::after(Duration::from_secs(sleep_in_secs)).await;
+
+        if let Err(()) = connect_and_subscribe(stack).await {
+            error!("MQTT session dropped, reconnecting...");
+            broadcast_net_status(NetStatus::FetchError);
+        }
+
+        sleep_state = SleepState::AfterFailure;
+    }
+}
+
+async fn connect_and_subscribe<D: Driver + 'static>(stack: &'static Stack<D>) -> Result<(), ()> {
+    let dns_client = DnsSocket::new(stack);
+    let addrs = dns_client
+        .query(BROKER_HOST, DnsQueryType::A)
+        .await
+        .map_err(|e| error!("Failed to resolve {}: {:?}", BROKER_HOST, e))?;
+    let addr = *addrs.first().ok_or_else(|| error!("No DNS results for {}", BROKER_HOST))?;
+
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(SOCKET_POLL_INTERVAL));
+    socket
+        .connect((addr, BROKER_PORT))
+        .await
+        .map_err(|e| error!("Failed to connect to broker: {:?}", e))?;
+
+    let mut config = ClientConfig::new(MqttVersion::MQTTv5, CountingRng(20000));
+    config.add_client_id(CLIENT_ID);
+    config.max_packet_size = 1024;
+
+    let mut recv_buffer = [0; 1024];
+    let mut write_buffer = [0; 1024];
+    let mut client = MqttClient::<_, 5, _>::new(
+        socket,
+        &mut write_buffer,
+        1024,
+        &mut recv_buffer,
+        1024,
+        config,
+    );
+
+    client
+        .connect_to_broker()
+        .await
+        .map_err(|e| error!("Failed to connect to broker: {:?}", e))?;
+    info!("connected to mqtt://{}:{}", BROKER_HOST, BROKER_PORT);
+
+    client
+        .subscribe_to_topic(SCORE_TOPIC)
+        .await
+        .map_err(|e| error!("Failed to subscribe to {}: {:?}", SCORE_TOPIC, e))?;
+    info!("subscribed to {}", SCORE_TOPIC);
+    broadcast_net_status(NetStatus::Ok);
+
+    // A PINGREQ is due on a fixed schedule measured from the last packet we
+    // sent, independent of how much (or how little) we're receiving — the
+    // broker's keep-alive timer only tracks traffic *from* the client, so a
+    // flood of incoming score updates must not postpone it. We never race
+    // `receive_message()` against a timer (dropping it mid-read could desync
+    // the client's parser on the next call); instead the socket's own read
+    // timeout bounds each call so this loop always gets a chance to check the
+    // ping deadline, whether or not a message arrived.
+    let mut last_sent = Instant::now();
+
+    loop {
+        match client.receive_message().await {
+            Ok((_topic, payload)) => handle_payload(payload),
+            Err(_) => {
+                // Either the socket's read timeout elapsed with nothing to
+                // read, or a transient hiccup; the PINGREQ below will detect
+                // a genuinely dead connection.
+            }
+        }
+
+        if Instant::now() - last_sent >= KEEP_ALIVE {
+            client
+                .send_ping()
+                .await
+                .map_err(|e| error!("Failed to send PINGREQ: {:?}", e))?;
+            last_sent = Instant::now();
+        }
+    }
+}
+
+fn handle_payload(payload: &[u8]) {
+    let Ok(body) = from_utf8(payload) else {
+        error!("Failed to decode MQTT payload as UTF-8");
+        return;
+    };
+    info!("Received score update: {:?}", &body);
+
+    match serde_json_core::de::from_slice::<GameResult>(body.as_bytes()) {
+        Ok((game_result, _used)) => {
+            let score = if let GameTime::WillBePlayed(_) = game_result.game_time {
+                None
+            } else {
+                Some((game_result.my_team_score, game_result.opponent_team_score))
+            };
+            SCORE_SIGNAL.signal(score);
+            TIME_SIGNAL.signal(TimeUpdate {
+                game_time: game_result.game_time,
+                opponent_team: game_result.opponent_team,
+            });
+        }
+        Err(e) => error!("Failed to parse payload: {}", e as u8),
+    }
+}
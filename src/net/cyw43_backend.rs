@@ -0,0 +1,96 @@
+use cyw43_pio::PioSpi;
+use defmt::{info, unwrap};
+use embassy_executor::Spawner;
+use embassy_net::{Config, Stack, StackResources};
+use embassy_rp::gpio::Output;
+use embassy_rp::peripherals::{DMA_CH0, PIO0};
+use static_cell::StaticCell;
+
+use super::{wait_for_stack_up, NetBackend};
+
+/// cyw43 Wi-Fi backend: joins a WPA2 network over the on-board PIO SPI link.
+pub struct Cyw43Backend {
+    pwr: Output<'static>,
+    spi: PioSpi<'static, PIO0, 0, DMA_CH0>,
+    fw: &'static [u8],
+    clm: &'static [u8],
+    network: &'static str,
+    password: &'static str,
+}
+
+impl Cyw43Backend {
+    pub fn new(
+        pwr: Output<'static>,
+        spi: PioSpi<'static, PIO0, 0, DMA_CH0>,
+        fw: &'static [u8],
+        clm: &'static [u8],
+        network: &'static str,
+        password: &'static str,
+    ) -> Self {
+        Self {
+            pwr,
+            spi,
+            fw,
+            clm,
+            network,
+            password,
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn wifi_task(
+    runner: cyw43::Runner<'static, Output<'static>, PioSpi<'static, PIO0, 0, DMA_CH0>>,
+) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task(pool_size = 1)]
+async fn net_task(stack: &'static Stack<cyw43::NetDriver<'static>>) -> ! {
+    stack.run().await
+}
+
+impl NetBackend for Cyw43Backend {
+    type Driver = cyw43::NetDriver<'static>;
+
+    async fn connect(self, spawner: Spawner, seed: u64) -> &'static Stack<Self::Driver> {
+        static STATE: StaticCell<cyw43::State> = StaticCell::new();
+        let state = STATE.init(cyw43::State::new());
+        let (net_device, mut control, runner) = cyw43::new(state, self.pwr, self.spi, self.fw).await;
+        unwrap!(spawner.spawn(wifi_task(runner)));
+
+        control.init(self.clm).await;
+        control
+            .set_power_management(cyw43::PowerManagementMode::PowerSave)
+            .await;
+
+        let config = Config::dhcpv4(Default::default());
+
+        static STACK: StaticCell<Stack<cyw43::NetDriver<'static>>> = StaticCell::new();
+        static RESOURCES: StaticCell<StackResources<5>> = StaticCell::new();
+        let stack = &*STACK.init(Stack::new(
+            net_device,
+            config,
+            RESOURCES.init(StackResources::<5>::new()),
+            seed,
+        ));
+
+        unwrap!(spawner.spawn(net_task(stack)));
+
+        crate::broadcast_net_status(crate::NetStatus::WifiJoining);
+        info!("connecting to WiFi...");
+        loop {
+            match control.join_wpa2(self.network, self.password).await {
+                Ok(_) => break,
+                Err(err) => {
+                    info!("join failed with status={}", err.status);
+                }
+            }
+        }
+
+        control.gpio_set(0, true).await;
+
+        wait_for_stack_up(stack).await;
+        stack
+    }
+}
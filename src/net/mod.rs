@@ -0,0 +1,48 @@
+use embassy_executor::Spawner;
+use embassy_net::driver::Driver;
+use embassy_net::Stack;
+use embassy_time::Timer;
+
+#[cfg(not(feature = "wiznet"))]
+mod cyw43_backend;
+#[cfg(feature = "wiznet")]
+mod wiznet_backend;
+
+#[cfg(not(feature = "wiznet"))]
+pub use cyw43_backend::Cyw43Backend;
+#[cfg(feature = "wiznet")]
+pub use wiznet_backend::W5500Backend;
+
+/// Brings up a network interface and hands back the running `Stack`.
+///
+/// Implementors own whatever peripherals/driver state they need; `connect`
+/// does the join/link-up dance and spawns whatever background tasks the
+/// driver requires, so callers never see cyw43 or W5500 specifics.
+pub trait NetBackend {
+    type Driver: Driver + 'static;
+
+    async fn connect(self, spawner: Spawner, seed: u64) -> &'static Stack<Self::Driver>;
+}
+
+// Each backend spawns its own concrete `net_task` over its own `Driver` type
+// rather than sharing one generic task function here: `#[embassy_executor::task]`
+// functions must not be generic.
+async fn wait_for_stack_up<D: Driver + 'static>(stack: &'static Stack<D>) {
+    crate::broadcast_net_status(crate::NetStatus::DhcpWait);
+    defmt::info!("waiting for DHCP...");
+    while !stack.is_config_up() {
+        Timer::after_millis(100).await;
+    }
+    defmt::info!("DHCP is now up!");
+
+    crate::broadcast_net_status(crate::NetStatus::LinkDown);
+    defmt::info!("waiting for link up...");
+    while !stack.is_link_up() {
+        Timer::after_millis(500).await;
+    }
+    defmt::info!("Link is up!");
+
+    defmt::info!("waiting for stack to be up...");
+    stack.wait_config_up().await;
+    defmt::info!("Stack is up!");
+}
@@ -0,0 +1,83 @@
+use defmt::unwrap;
+use embassy_executor::Spawner;
+use embassy_net::{Config, Stack, StackResources};
+use embassy_net_wiznet::chip::W5500;
+use embassy_net_wiznet::{Device, Runner, State};
+use embassy_rp::gpio::{Input, Output};
+use embassy_rp::peripherals::SPI0;
+use embassy_rp::spi::{Async, Spi};
+use embassy_time::Delay;
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+use embedded_hal_bus::spi::ExclusiveDevice;
+use static_cell::StaticCell;
+
+use super::{wait_for_stack_up, NetBackend};
+
+/// W5500 wired-Ethernet backend, driven over SPI in MACRAW mode. Useful at
+/// venues where Wi-Fi is unreliable or unavailable.
+pub struct W5500Backend<SPI> {
+    spi: SPI,
+    int: Input<'static>,
+    reset: Output<'static>,
+    mac_addr: [u8; 6],
+}
+
+impl<SPI> W5500Backend<SPI>
+where
+    SPI: SpiDevice + 'static,
+{
+    pub fn new(spi: SPI, int: Input<'static>, reset: Output<'static>, mac_addr: [u8; 6]) -> Self {
+        Self {
+            spi,
+            int,
+            reset,
+            mac_addr,
+        }
+    }
+}
+
+// The SPI device `main` wires the W5500 to. Task functions must not be
+// generic, so `eth_task` (and the `NetBackend` impl below) name this type.
+type W5500Spi = ExclusiveDevice<Spi<'static, SPI0, Async>, Output<'static>, Delay>;
+
+#[embassy_executor::task(pool_size = 1)]
+async fn eth_task(
+    runner: Runner<'static, W5500, W5500Spi, Input<'static>, Output<'static>>,
+) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task(pool_size = 1)]
+async fn net_task(stack: &'static Stack<Device<'static>>) -> ! {
+    stack.run().await
+}
+
+impl NetBackend for W5500Backend<W5500Spi> {
+    type Driver = Device<'static>;
+
+    async fn connect(self, spawner: Spawner, seed: u64) -> &'static Stack<Self::Driver> {
+        static STATE: StaticCell<State<8, 8>> = StaticCell::new();
+        let state = STATE.init(State::<8, 8>::new());
+        let (device, runner) = unwrap!(
+            embassy_net_wiznet::new(self.mac_addr, state, self.spi, self.int, self.reset).await
+        );
+        unwrap!(spawner.spawn(eth_task(runner)));
+
+        let config = Config::dhcpv4(Default::default());
+
+        static STACK: StaticCell<Stack<Device<'static>>> = StaticCell::new();
+        static RESOURCES: StaticCell<StackResources<5>> = StaticCell::new();
+        let stack = &*STACK.init(Stack::new(
+            device,
+            config,
+            RESOURCES.init(StackResources::<5>::new()),
+            seed,
+        ));
+
+        unwrap!(spawner.spawn(net_task(stack)));
+
+        wait_for_stack_up(stack).await;
+        stack
+    }
+}
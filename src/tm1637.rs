@@ -1,5 +1,5 @@
 use embassy_rp::gpio::OutputOpenDrain;
-use embassy_time::Timer;
+use embassy_time::{Duration, Timer};
 
 const DELAY_USECS: u64 = 100;
 
@@ -53,6 +53,75 @@ pub(crate) fn get_digit_code(digit: Option<u64>) -> u8 {
     }
 }
 
+// Approximate lower-case glyphs for a 7-segment display, indexed 'a'..='z'.
+// Letters that have no sane 7-segment rendering (k, m, v, w, x) borrow the
+// closest-looking neighbour.
+const ALPHA: [u8; 26] = [
+    0b01110111, // a
+    0b01111100, // b
+    0b00111001, // c
+    0b01011110, // d
+    0b01111001, // e
+    0b01110001, // f
+    0b01101111, // g
+    0b01110100, // h
+    0b00000100, // i
+    0b00011110, // j
+    0b01110110, // k (looks like H)
+    0b00111000, // l
+    0b01010100, // m (looks like n)
+    0b01010100, // n
+    0b01011100, // o
+    0b01110011, // p
+    0b01100111, // q
+    0b01010000, // r
+    0b01101101, // s
+    0b01111000, // t
+    0b00011100, // u
+    0b00011100, // v (looks like u)
+    0b00011100, // w (looks like u)
+    0b01110110, // x (looks like H)
+    0b01101110, // y
+    0b01011011, // z (looks like 2)
+];
+
+const DASH: u8 = 0b01000000;
+
+/// Strips common Czech diacritics down to their base Latin letter (e.g.
+/// 'ě' -> 'e', 'š' -> 's') so opponent names like "Plzeň" or "Baník" scroll
+/// with a readable approximation instead of a blank gap where the accented
+/// character would otherwise fall outside the font.
+fn transliterate(c: char) -> char {
+    match c {
+        'á' | 'Á' => 'a',
+        'č' | 'Č' => 'c',
+        'ď' | 'Ď' => 'd',
+        'é' | 'É' | 'ě' | 'Ě' => 'e',
+        'í' | 'Í' => 'i',
+        'ň' | 'Ň' => 'n',
+        'ó' | 'Ó' => 'o',
+        'ř' | 'Ř' => 'r',
+        'š' | 'Š' => 's',
+        'ť' | 'Ť' => 't',
+        'ú' | 'Ú' | 'ů' | 'Ů' => 'u',
+        'ý' | 'Ý' => 'y',
+        'ž' | 'Ž' => 'z',
+        other => other,
+    }
+}
+
+/// Maps a single character to its segment pattern, transliterating common
+/// Czech diacritics first; unknown characters (and space) render blank.
+pub(crate) fn get_char_code(c: char) -> u8 {
+    match transliterate(c) {
+        c @ '0'..='9' => DIGITS[(c as u8 - b'0') as usize],
+        c @ 'a'..='z' => ALPHA[(c as u8 - b'a') as usize],
+        c @ 'A'..='Z' => ALPHA[(c as u8 - b'A') as usize],
+        '-' => DASH,
+        _ => 0x0,
+    }
+}
+
 pub(crate) struct TM1637<'clk, 'dio> {
     clk: OutputOpenDrain<'clk>,
     dio: OutputOpenDrain<'dio>,
@@ -155,4 +224,35 @@ impl<'clk, 'dio> TM1637<'clk, 'dio> {
         }
         self.set_brightness(brightness, true).await;
     }
+
+    /// Shifts a 4-character window of `text` across the display, one glyph
+    /// at a time, yielding `step` between frames. Strings of 4 glyphs or
+    /// fewer are padded (not scrolled); characters outside the font render
+    /// blank.
+    pub async fn scroll_text(&mut self, text: &str, step: Duration) {
+        const MAX_GLYPHS: usize = 64;
+
+        let mut glyphs = [0u8; MAX_GLYPHS];
+        let mut len = 0;
+        for c in text.chars().take(MAX_GLYPHS) {
+            glyphs[len] = get_char_code(c);
+            len += 1;
+        }
+
+        if len <= 4 {
+            let mut frame = [0u8; 4];
+            frame[..len].copy_from_slice(&glyphs[..len]);
+            self.display(frame, false, crate::DEFAULT_BRIGHTNESS_LEVEL)
+                .await;
+            return;
+        }
+
+        for start in 0..=(len - 4) {
+            let mut frame = [0u8; 4];
+            frame.copy_from_slice(&glyphs[start..start + 4]);
+            self.display(frame, false, crate::DEFAULT_BRIGHTNESS_LEVEL)
+                .await;
+            Timer::after(step).await;
+        }
+    }
 }